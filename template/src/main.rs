@@ -1,11 +1,18 @@
 use std::fs;
 use std::path::PathBuf;
 use std::env;
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Expr, Fields, Ident, Item, ItemEnum, ItemMod, ItemStruct, Token, Type};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ProgramInfo {
     program_id: String,
+    program_name: String,
     instructions: Vec<InstructionInfo>,
     accounts: Vec<AccountInfo>,
     errors: Vec<ErrorInfo>,
@@ -15,6 +22,10 @@ struct ProgramInfo {
 #[derive(Debug, Serialize, Deserialize)]
 struct InstructionInfo {
     name: String,
+    /// 对应的 `Context<T>` 账户结构名，用于在 IDL 里展开每条指令的账户列表。
+    context: String,
+    /// Anchor 指令判别符：`sha256("global:" + fn_name)` 的前 8 个字节。
+    discriminator: [u8; 8],
     arguments: Vec<ArgumentInfo>,
 }
 
@@ -27,6 +38,9 @@ struct ArgumentInfo {
 #[derive(Debug, Serialize, Deserialize)]
 struct AccountInfo {
     name: String,
+    /// `#[instruction(id: u64, ...)]` 声明的指令参数，种子表达式常常引用它们。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    instruction_args: Vec<ArgumentInfo>,
     fields: Vec<FieldInfo>,
 }
 
@@ -34,6 +48,41 @@ struct AccountInfo {
 struct FieldInfo {
     name: String,
     type_name: String,
+    /// 解析自字段上的 `#[account(...)]`；普通状态结构体的字段没有这个属性，保持默认空值。
+    #[serde(skip_serializing_if = "AccountConstraints::is_empty")]
+    constraints: AccountConstraints,
+}
+
+/// `#[account(...)]` 里描述 PDA 派生与初始化行为的约束。
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AccountConstraints {
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_mut: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_signer: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_init: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    bump: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    space: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    seeds: Vec<String>,
+}
+
+impl AccountConstraints {
+    /// 没有任何约束时视为空，用于在 JSON 里整体省略该字段。
+    fn is_empty(&self) -> bool {
+        !self.is_mut
+            && !self.is_signer
+            && !self.is_init
+            && !self.bump
+            && self.payer.is_none()
+            && self.space.is_none()
+            && self.seeds.is_empty()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,6 +95,12 @@ struct ErrorInfo {
 #[derive(Debug, Serialize, Deserialize)]
 struct StructInfo {
     name: String,
+    /// 带有 `#[account]` 的结构体是链上账户状态，其余只是普通类型。
+    is_account: bool,
+    /// 账户判别符：`sha256("account:" + StructName)` 的前 8 个字节。
+    /// 仅对 `#[account]` 状态结构体有意义，其余为 `None`。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    discriminator: Option<[u8; 8]>,
     fields: Vec<FieldInfo>,
 }
 
@@ -53,144 +108,1044 @@ fn main() {
     let args: Vec<String> = env::args().collect();
 
     if args.len() > 1 && args[1] == "dump_info" {
-        dump_program_info();
+        let rest = &args[2..];
+        // `dump_info --idl` 输出标准 Anchor IDL，否则输出内部的 ProgramInfo。
+        let as_idl = rest.iter().any(|arg| arg == "--idl");
+        // `--program <path>` 显式指定 lib.rs 路径，覆盖自动探测。
+        let explicit_program = rest
+            .iter()
+            .position(|arg| arg == "--program")
+            .and_then(|i| rest.get(i + 1))
+            .map(String::as_str);
+        dump_program_info(as_idl, explicit_program);
     } else {
         // 默认行为：什么都不做
         println!("Solana Swap Program - Use 'dump_info' command to export program definition");
     }
 }
 
-fn dump_program_info() {
+fn dump_program_info(as_idl: bool, explicit_program: Option<&str>) {
     let project_root = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-    let lib_path = project_root.join("programs/swap-program/src/lib.rs");
+    let lib_path = find_lib_path(&project_root, explicit_program)
+        .unwrap_or_else(|| project_root.join("programs/swap-program/src/lib.rs"));
 
     let lib_content = fs::read_to_string(&lib_path).unwrap_or_else(|_| {
         eprintln!("Warning: Could not read lib.rs at {:?}", lib_path);
         String::new()
     });
 
-    let program_info = parse_program_info(&lib_content);
+    // lib.rs 所在目录，用于解析 `mod foo;` 这类按文件拆分的子模块。
+    let src_dir = lib_path
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let program_info = parse_program_info(&lib_content, &src_dir);
+
+    if as_idl {
+        let idl = build_idl(&program_info);
+        println!("{}", serde_json::to_string_pretty(&idl).unwrap());
+    } else {
+        println!("{}", serde_json::to_string_pretty(&program_info).unwrap());
+    }
+}
 
-    println!("{}", serde_json::to_string_pretty(&program_info).unwrap());
+/// 定位program 包的 `lib.rs`：优先使用 `--program` 显式指定的路径，否则在
+/// `programs/*/src/lib.rs` 下搜索（Anchor workspace 的 program 目录名因项目而异，
+/// 如这里的 `programs/swap`，不能硬编码为 `programs/swap-program`）。
+fn find_lib_path(project_root: &std::path::Path, explicit: Option<&str>) -> Option<PathBuf> {
+    if let Some(explicit) = explicit {
+        let path = PathBuf::from(explicit);
+        let path = if path.is_absolute() {
+            path
+        } else {
+            project_root.join(path)
+        };
+        if !path.exists() {
+            // 明确指出 `--program` 被拒绝，而不是悄悄回退到默认路径，
+            // 让用户误以为拿到的是自己指定的那个 program。
+            eprintln!("Warning: --program path {path:?} does not exist, falling back to auto-detection");
+            return None;
+        }
+        return Some(path);
+    }
+
+    let mut candidates: Vec<PathBuf> = fs::read_dir(project_root.join("programs"))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join("src").join("lib.rs"))
+        .filter(|path| path.exists())
+        .collect();
+    candidates.sort();
+    candidates.into_iter().next()
 }
 
-fn parse_program_info(content: &str) -> ProgramInfo {
+fn parse_program_info(content: &str, src_dir: &std::path::Path) -> ProgramInfo {
     let mut program_info = ProgramInfo {
         program_id: String::new(),
+        program_name: String::new(),
         instructions: Vec::new(),
         accounts: Vec::new(),
         errors: Vec::new(),
         structs: Vec::new(),
     };
 
-    // 解析 program_id
-    if let Some(captures) = regex::Regex::new(r#"declare_id!\("([^"]+)"\)"#).unwrap().captures(content) {
-        if let Some(id) = captures.get(1) {
-            program_info.program_id = id.as_str().to_string();
+    // 解析失败时退回到一个空定义，避免中断整个 dump。
+    let file = match syn::parse_file(content) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Warning: Could not parse lib.rs as Rust: {}", err);
+            return program_info;
         }
-    }
+    };
+
+    collect_items(&file.items, src_dir, &mut program_info);
 
-    // 解析指令 (pub fn)
-    let instruction_re = regex::Regex::new(r#"pub fn (\w+)\(ctx: Context<([^>]+)>(?:, ([^)]+))?\)"#).unwrap();
-    for caps in instruction_re.captures_iter(content) {
-        let name = caps.get(1).map(|m| m.as_str().to_string()).unwrap_or_default();
-        let _context = caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default();
-        let args_str = caps.get(3).map(|m| m.as_str().to_string()).unwrap_or_default();
+    program_info
+}
 
-        let mut arguments = Vec::new();
-        if !args_str.is_empty() {
-            for arg in args_str.split(',') {
-                let arg = arg.trim();
-                if let Some((name_part, type_part)) = arg.split_once(':') {
-                    arguments.push(ArgumentInfo {
-                        name: name_part.trim().to_string(),
-                        type_name: type_part.trim().to_string(),
+/// 遍历一组 item，并在遇到 `#[program]` 模块时收集其中的指令。
+///
+/// `dir` 是当前这层模块用来解析按文件拆分的子模块的目录。
+fn collect_items(items: &[Item], dir: &std::path::Path, program_info: &mut ProgramInfo) {
+    for item in items {
+        match item {
+            Item::Macro(item_macro) if item_macro.mac.path.is_ident("declare_id") => {
+                if let Ok(lit) = item_macro.mac.parse_body::<syn::LitStr>() {
+                    program_info.program_id = lit.value();
+                }
+            }
+            Item::Mod(item_mod) if has_attr(&item_mod.attrs, "program") => {
+                program_info.program_name = item_mod.ident.to_string();
+                collect_instructions(item_mod, program_info);
+            }
+            Item::Mod(item_mod) => {
+                match &item_mod.content {
+                    // 内联模块：原地下钻。
+                    Some((_, nested)) => collect_items(nested, dir, program_info),
+                    // `mod foo;`：从磁盘加载 foo.rs（如 instructions/make_offer.rs）。
+                    None => load_module_file(dir, &item_mod.ident.to_string(), program_info),
+                }
+            }
+            Item::Struct(item_struct) => {
+                if has_derive(&item_struct.attrs, "Accounts") {
+                    program_info.accounts.push(AccountInfo {
+                        name: item_struct.ident.to_string(),
+                        instruction_args: instruction_args(&item_struct.attrs),
+                        fields: struct_fields(&item_struct.fields),
                     });
+                } else {
+                    program_info.structs.push(collect_struct(item_struct));
                 }
             }
+            Item::Enum(item_enum) if is_error_enum(item_enum) => {
+                collect_errors(item_enum, program_info);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 解析 `mod name;` 指向的文件：优先 `dir/name.rs`，否则 `dir/name/mod.rs`。
+/// 子模块内部再拆分的文件则以 `dir/name/` 为基准继续解析。
+fn load_module_file(dir: &std::path::Path, name: &str, program_info: &mut ProgramInfo) {
+    let (path, child_dir) = {
+        let flat = dir.join(format!("{name}.rs"));
+        if flat.exists() {
+            (flat, dir.join(name))
+        } else {
+            (dir.join(name).join("mod.rs"), dir.join(name))
         }
+    };
+
+    let Ok(content) = fs::read_to_string(&path) else {
+        return;
+    };
+    if let Ok(file) = syn::parse_file(&content) {
+        collect_items(&file.items, &child_dir, program_info);
+    }
+}
+
+/// Anchor 的错误枚举带 `#[error_code]`；这里也兼容直接命名为 `Error` 的枚举。
+fn is_error_enum(item_enum: &ItemEnum) -> bool {
+    has_attr(&item_enum.attrs, "error_code") || item_enum.ident == "Error"
+}
+
+/// 收集 `#[program]` 模块里的 `pub fn`，作为程序指令。
+fn collect_instructions(item_mod: &ItemMod, program_info: &mut ProgramInfo) {
+    let Some((_, items)) = &item_mod.content else {
+        return;
+    };
+
+    for item in items {
+        let Item::Fn(item_fn) = item else {
+            continue;
+        };
+        if !matches!(item_fn.vis, syn::Visibility::Public(_)) {
+            continue;
+        }
+        // Anchor 指令的第一个参数必定是 `ctx: Context<...>`；否则只是普通辅助函数。
+        let first_is_context = matches!(
+            item_fn.sig.inputs.first(),
+            Some(syn::FnArg::Typed(pat_type)) if is_context_type(&pat_type.ty)
+        );
+        if !first_is_context {
+            continue;
+        }
+        let context = match item_fn.sig.inputs.first() {
+            Some(syn::FnArg::Typed(pat_type)) => {
+                context_struct_name(&pat_type.ty).unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+
+        // 第一个参数是 `ctx: Context<...>`，跳过它，其余才是指令参数。
+        let arguments = item_fn
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                syn::FnArg::Typed(pat_type) if !is_context_type(&pat_type.ty) => {
+                    if let syn::Pat::Ident(pat_ident) = pat_type.pat.as_ref() {
+                        Some(ArgumentInfo {
+                            name: pat_ident.ident.to_string(),
+                            type_name: type_to_string(&pat_type.ty),
+                        })
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            })
+            .collect();
 
+        let name = item_fn.sig.ident.to_string();
+        // Anchor 不会对函数名再做 snake_case 转换，直接使用原名。
+        let discriminator = discriminator("global:", &name);
         program_info.instructions.push(InstructionInfo {
             name,
+            context,
+            discriminator,
             arguments,
         });
     }
+}
 
-    // 解析账户结构 (#[derive(Accounts)] pub struct)
-    let account_re = regex::Regex::new(r#"#\[derive\(Accounts\)\]\s+pub struct (\w+)<[^>]*>\s*\{([^}]+)\}"#).unwrap();
-    for caps in account_re.captures_iter(content) {
-        let name = caps.get(1).map(|m| m.as_str().to_string()).unwrap_or_default();
-        let fields_str = caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default();
+fn collect_struct(item_struct: &ItemStruct) -> StructInfo {
+    let name = item_struct.ident.to_string();
+    let is_account = has_attr(&item_struct.attrs, "account");
+    // 账户名原样参与哈希，不做任何重命名。
+    let discriminator = is_account.then(|| discriminator("account:", &name));
+    StructInfo {
+        name,
+        is_account,
+        discriminator,
+        fields: struct_fields(&item_struct.fields),
+    }
+}
 
-        let mut fields = Vec::new();
-        let mut current_field = String::new();
+/// 计算 Anchor 判别符：`sha256(prefix + name)` 的前 8 个字节。
+fn discriminator(prefix: &str, name: &str) -> [u8; 8] {
+    let mut hasher = Sha256::new();
+    hasher.update(prefix.as_bytes());
+    hasher.update(name.as_bytes());
+    let hash = hasher.finalize();
+    let mut disc = [0u8; 8];
+    disc.copy_from_slice(&hash[..8]);
+    disc
+}
 
-        for line in fields_str.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
+fn collect_errors(item_enum: &ItemEnum, program_info: &mut ProgramInfo) {
+    // Anchor 错误码 = 6000 + 枚举判别值。判别值遵循 Rust 语义：默认从 0 递增，
+    // 显式 `= N` 会把计数器重置为 N，其后变体在此基础上继续递增。
+    let mut discriminant: u32 = 0;
+    for variant in &item_enum.variants {
+        if let Some((_, expr)) = &variant.discriminant {
+            if let Some(value) = literal_u32(expr) {
+                discriminant = value;
             }
+        }
+        let name = variant.ident.to_string();
+        // 变体自带 `#[msg("...")]` 时用它作为人类可读信息，否则退回变体名。
+        let message = variant_msg(&variant.attrs).unwrap_or_else(|| name.clone());
+        program_info.errors.push(ErrorInfo {
+            code: 6000 + discriminant,
+            name,
+            message,
+        });
+        discriminant += 1;
+    }
+}
 
-            // 如果是属性行，继续累积
-            if line.starts_with('#') {
-                current_field.push_str(line);
-                current_field.push(' ');
+/// 取出变体上的 `#[msg("...")]` 文本。
+fn variant_msg(attrs: &[syn::Attribute]) -> Option<String> {
+    let attr = attrs.iter().find(|attr| attr.path().is_ident("msg"))?;
+    attr.parse_args::<syn::LitStr>().ok().map(|lit| lit.value())
+}
+
+/// 把形如 `= 10` 的显式判别值解析为 `u32`。
+fn literal_u32(expr: &Expr) -> Option<u32> {
+    if let Expr::Lit(expr_lit) = expr {
+        if let syn::Lit::Int(int) = &expr_lit.lit {
+            return int.base10_parse::<u32>().ok();
+        }
+    }
+    None
+}
+
+fn struct_fields(fields: &Fields) -> Vec<FieldInfo> {
+    let Fields::Named(named) = fields else {
+        return Vec::new();
+    };
+
+    named
+        .named
+        .iter()
+        .filter_map(|field| {
+            field.ident.as_ref().map(|ident| FieldInfo {
+                name: ident.to_string(),
+                type_name: type_to_string(&field.ty),
+                constraints: account_constraints(&field.attrs, &ident.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// 读取字段上的 `#[account(...)]`，解析出 PDA 派生与初始化相关的约束。
+/// 字段没有该属性（普通状态结构体）时返回默认空约束。
+fn account_constraints(attrs: &[syn::Attribute], field_name: &str) -> AccountConstraints {
+    let mut constraints = AccountConstraints::default();
+    let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident("account")) else {
+        return constraints;
+    };
+    let parsed = match attr.parse_args::<AccountAttr>() {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!(
+                "Warning: Could not parse #[account(...)] on field `{field_name}`: {err}"
+            );
+            return constraints;
+        }
+    };
+
+    for entry in parsed.0 {
+        match entry {
+            AccountEntry::Flag(flag) => match flag.as_str() {
+                "mut" => constraints.is_mut = true,
+                "signer" => constraints.is_signer = true,
+                "init" | "init_if_needed" => constraints.is_init = true,
+                "bump" => constraints.bump = true,
+                _ => {}
+            },
+            AccountEntry::Pair(key, value) => match key.as_str() {
+                "payer" => constraints.payer = Some(value),
+                "space" => constraints.space = Some(value),
+                // `bump = expr` 既声明 bump 也给出表达式，这里只记录存在性。
+                "bump" => constraints.bump = true,
+                _ => {}
+            },
+            AccountEntry::Seeds(seeds) => constraints.seeds = seeds,
+        }
+    }
+
+    constraints
+}
+
+/// 解析 `#[derive(Accounts)]` 结构体上的 `#[instruction(id: u64, ...)]`。
+fn instruction_args(attrs: &[syn::Attribute]) -> Vec<ArgumentInfo> {
+    let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident("instruction")) else {
+        return Vec::new();
+    };
+    let Ok(args) = attr.parse_args_with(Punctuated::<syn::FnArg, Token![,]>::parse_terminated)
+    else {
+        return Vec::new();
+    };
+
+    args.iter()
+        .filter_map(|arg| match arg {
+            syn::FnArg::Typed(pat_type) => {
+                let syn::Pat::Ident(pat_ident) = pat_type.pat.as_ref() else {
+                    return None;
+                };
+                Some(ArgumentInfo {
+                    name: pat_ident.ident.to_string(),
+                    type_name: type_to_string(&pat_type.ty),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_context_type(ty: &Type) -> bool {
+    if let Type::Path(type_path) = ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            return segment.ident == "Context";
+        }
+    }
+    false
+}
+
+/// 从 `Context<MakeOffer>` 中取出内层账户结构名 `MakeOffer`。
+fn context_struct_name(ty: &Type) -> Option<String> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Context" {
+        return None;
+    }
+    if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+        for arg in &args.args {
+            if let syn::GenericArgument::Type(Type::Path(inner)) = arg {
+                if let Some(inner_seg) = inner.path.segments.last() {
+                    return Some(inner_seg.ident.to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+fn type_to_string(ty: &Type) -> String {
+    // syn 的 token 流会在各 token 间插入空格，这里把常见的泛型写法收敛回紧凑形式。
+    ty.to_token_stream()
+        .to_string()
+        .replace(" < ", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+        .replace("& ", "&")
+        .replace(" :: ", "::")
+}
+
+/// 把 token 流收敛回紧凑形式，用于记录 `seeds`/`space`/`payer`/`has_one` 等约束值。
+fn tokens_to_string(tokens: impl ToTokens) -> String {
+    tokens
+        .to_token_stream()
+        .to_string()
+        .replace(" . ", ".")
+        .replace("( ", "(")
+        .replace(" (", "(")
+        .replace(" )", ")")
+        .replace(" ,", ",")
+        .replace(" :: ", "::")
+        .replace(" < ", "<")
+        .replace(" >", ">")
+}
+
+fn expr_to_string(expr: &Expr) -> String {
+    tokens_to_string(expr)
+}
+
+/// `#[account(...)]` 里逗号分隔的一条约束。
+enum AccountEntry {
+    /// 无值标志，如 `init`、`mut`、`bump`。
+    Flag(String),
+    /// `key = expr` 形式，如 `payer = maker`、`space = 8 + Offer::INIT_SPACE`。
+    Pair(String, String),
+    /// `seeds = [..]`，逐个种子表达式展开。
+    Seeds(Vec<String>),
+}
+
+/// `#[account(...)]` 约束列表的解析器。`mut` 是关键字，需单独处理。
+struct AccountAttr(Vec<AccountEntry>);
+
+impl Parse for AccountAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut entries = Vec::new();
+        while !input.is_empty() {
+            if input.peek(Token![mut]) {
+                input.parse::<Token![mut]>()?;
+                entries.push(AccountEntry::Flag("mut".to_string()));
             } else {
-                // 如果是字段定义行
-                if let Some((name_part, type_part)) = line.split_once(':') {
-                    let field_name = name_part.split_whitespace().last().unwrap_or("").to_string();
-                    fields.push(FieldInfo {
-                        name: field_name,
-                        type_name: type_part.trim().to_string(),
-                    });
+                let key = parse_constraint_key(input)?;
+                if input.peek(Token![=]) {
+                    input.parse::<Token![=]>()?;
+                    // 不要求值是合法的 `syn::Expr`：Anchor 的
+                    // `has_one = maker @ SwapError::NotAuthorized` /
+                    // `constraint = ... @ SwapError::Variant` 用到的 `@` 不是合法
+                    // 的 Rust 表达式语法，严格按 `Expr` 解析会让整个属性解析失败，
+                    // 导致该字段的其余约束（如 `mut`）也一并丢失。这里只读到下一个
+                    // 顶层逗号为止的原始 token 流，尽量保留约束的语义。
+                    let raw = parse_raw_value(input)?;
+                    if key == "seeds" {
+                        let seeds = match syn::parse2::<Expr>(raw.clone()) {
+                            Ok(Expr::Array(array)) => {
+                                array.elems.iter().map(expr_to_string).collect()
+                            }
+                            _ => vec![tokens_to_string(&raw)],
+                        };
+                        entries.push(AccountEntry::Seeds(seeds));
+                    } else {
+                        entries.push(AccountEntry::Pair(key, tokens_to_string(&raw)));
+                    }
+                } else {
+                    entries.push(AccountEntry::Flag(key));
                 }
-                current_field.clear();
+            }
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
             }
         }
+        Ok(AccountAttr(entries))
+    }
+}
+
+/// 解析约束的 key：普通 `mut`/`init` 之外，Anchor 还有 `realloc::payer`、
+/// `realloc::zero` 这类路径形式的 key，`::` 不是 `Ident` 的一部分，需要单独拼接。
+fn parse_constraint_key(input: ParseStream) -> syn::Result<String> {
+    let mut key = input.parse::<Ident>()?.to_string();
+    while input.peek(Token![::]) {
+        input.parse::<Token![::]>()?;
+        key.push_str("::");
+        key.push_str(&input.parse::<Ident>()?.to_string());
+    }
+    Ok(key)
+}
+
+/// 读取从当前位置到下一个顶层逗号（或输入末尾）为止的原始 token 流，不对其语法
+/// 做任何假设。括号/方括号/花括号内部在 `proc_macro2` 里天然是单个 token tree，
+/// 所以这里只需要在当前层级上寻找逗号，不用手动维护括号深度。
+fn parse_raw_value(input: ParseStream) -> syn::Result<TokenStream> {
+    input.step(|cursor| {
+        let mut tokens = TokenStream::new();
+        let mut rest = *cursor;
+        while let Some((tt, next)) = rest.token_tree() {
+            if matches!(&tt, TokenTree::Punct(p) if p.as_char() == ',') {
+                break;
+            }
+            tokens.extend(std::iter::once(tt));
+            rest = next;
+        }
+        Ok((tokens, rest))
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Anchor IDL 输出
+//
+// 把内部的 ProgramInfo 转换成 `@coral-xyz/anchor` 能直接消费的 IDL 结构。
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Idl {
+    version: String,
+    name: String,
+    instructions: Vec<IdlInstruction>,
+    accounts: Vec<IdlTypeDef>,
+    types: Vec<IdlTypeDef>,
+    errors: Vec<IdlError>,
+    metadata: IdlMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IdlInstruction {
+    name: String,
+    discriminator: Vec<u8>,
+    accounts: Vec<IdlAccountItem>,
+    args: Vec<IdlField>,
+}
+
+/// IDL 指令账户树的一项：要么是单个账户，要么是组合账户（嵌套的 Accounts 组）。
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum IdlAccountItem {
+    Single(IdlAccount),
+    Composite(IdlAccounts),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IdlAccount {
+    name: String,
+    is_mut: bool,
+    is_signer: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_optional: bool,
+}
+
+/// 组合账户：字段类型本身是另一个 `#[derive(Accounts)]` 结构时的嵌套组。
+#[derive(Debug, Serialize, Deserialize)]
+struct IdlAccounts {
+    name: String,
+    accounts: Vec<IdlAccountItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IdlTypeDef {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    discriminator: Option<Vec<u8>>,
+    #[serde(rename = "type")]
+    ty: IdlTypeDefTy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IdlTypeDefTy {
+    kind: String,
+    fields: Vec<IdlField>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IdlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IdlError {
+    code: u32,
+    name: String,
+    msg: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IdlMetadata {
+    address: String,
+}
+
+fn build_idl(info: &ProgramInfo) -> Idl {
+    let instructions = info
+        .instructions
+        .iter()
+        .map(|ix| IdlInstruction {
+            name: ix.name.clone(),
+            discriminator: ix.discriminator.to_vec(),
+            accounts: idl_accounts_for(info, &ix.context),
+            args: ix
+                .arguments
+                .iter()
+                .map(|arg| IdlField {
+                    name: arg.name.clone(),
+                    ty: map_idl_type(&arg.type_name),
+                })
+                .collect(),
+        })
+        .collect();
+
+    // `#[account]` 结构体进 accounts，其余普通结构体进 types。
+    let accounts = info
+        .structs
+        .iter()
+        .filter(|s| s.is_account)
+        .map(struct_to_type_def)
+        .collect();
+    let types = info
+        .structs
+        .iter()
+        .filter(|s| !s.is_account)
+        .map(struct_to_type_def)
+        .collect();
+
+    let errors = info
+        .errors
+        .iter()
+        .map(|err| IdlError {
+            code: err.code,
+            name: err.name.clone(),
+            msg: err.message.clone(),
+        })
+        .collect();
+
+    Idl {
+        version: "0.1.0".to_string(),
+        name: info.program_name.clone(),
+        instructions,
+        accounts,
+        types,
+        errors,
+        metadata: IdlMetadata {
+            address: info.program_id.clone(),
+        },
+    }
+}
+
+/// 根据指令的 `Context<T>` 展开账户列表；从 `T` 的字段类型推断 signer/optional。
+fn idl_accounts_for(info: &ProgramInfo, context: &str) -> Vec<IdlAccountItem> {
+    idl_accounts_expand(info, context, &mut Vec::new())
+}
 
-        program_info.accounts.push(AccountInfo { name, fields });
+/// 递归展开 `context` 的账户字段；`seen` 记录展开路径，避免组合账户成环时无限递归。
+fn idl_accounts_expand(
+    info: &ProgramInfo,
+    context: &str,
+    seen: &mut Vec<String>,
+) -> Vec<IdlAccountItem> {
+    let Some(account) = info.accounts.iter().find(|a| a.name == context) else {
+        return Vec::new();
+    };
+    if seen.iter().any(|name| name == context) {
+        return Vec::new();
     }
+    seen.push(context.to_string());
 
-    // 解析错误 (pub enum Error)
-    let error_re = regex::Regex::new(r#"pub enum Error\s*\{([^}]+)\}"#).unwrap();
-    if let Some(caps) = error_re.captures(content) {
-        let errors_str = caps.get(1).map(|m| m.as_str().to_string()).unwrap_or_default();
-        for (idx, line) in errors_str.lines().enumerate() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
+    let items = account
+        .fields
+        .iter()
+        .map(|field| {
+            let base = base_type_ident(&field.type_name);
+            // 字段类型本身是另一个 `#[derive(Accounts)]` 结构：作为组合账户内联展开。
+            if info.accounts.iter().any(|a| a.name == base) {
+                IdlAccountItem::Composite(IdlAccounts {
+                    name: field.name.clone(),
+                    accounts: idl_accounts_expand(info, &base, seen),
+                })
+            } else {
+                let stripped = strip_option(&field.type_name);
+                let c = &field.constraints;
+                IdlAccountItem::Single(IdlAccount {
+                    // `init` 隐含账户可写，并入 `is_mut`。
+                    is_mut: c.is_mut || c.is_init,
+                    is_signer: c.is_signer || stripped.contains("Signer"),
+                    is_optional: stripped != field.type_name,
+                    name: field.name.clone(),
+                })
+            }
+        })
+        .collect();
+
+    seen.pop();
+    items
+}
+
+/// 取类型字符串里最外层的类型名，如 `TransferAccounts<'info>` → `TransferAccounts`。
+fn base_type_ident(type_name: &str) -> String {
+    type_name
+        .trim_start_matches('&')
+        .split('<')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+fn struct_to_type_def(info: &StructInfo) -> IdlTypeDef {
+    IdlTypeDef {
+        name: info.name.clone(),
+        discriminator: info.discriminator.map(|d| d.to_vec()),
+        ty: IdlTypeDefTy {
+            kind: "struct".to_string(),
+            fields: info
+                .fields
+                .iter()
+                .map(|field| IdlField {
+                    name: field.name.clone(),
+                    ty: map_idl_type(&field.type_name),
+                })
+                .collect(),
+        },
+    }
+}
+
+/// 若类型是 `Option<T>` 则返回内层 `T`，否则原样返回。
+fn strip_option(type_name: &str) -> &str {
+    wrapped(type_name, "Option").unwrap_or(type_name)
+}
+
+/// 把（紧凑形式的）Rust 类型字符串映射成 Anchor IDL 类型。
+fn map_idl_type(type_name: &str) -> serde_json::Value {
+    use serde_json::json;
+
+    let name = type_name.trim().trim_start_matches('&');
+    // 去掉引用里的生命周期与 `mut`。
+    let name = name
+        .trim_start_matches("'info")
+        .trim_start()
+        .trim_start_matches("mut")
+        .trim();
+
+    if let Some(inner) = wrapped(name, "Option") {
+        return json!({ "option": map_idl_type(inner) });
+    }
+    if let Some(inner) = wrapped(name, "Vec") {
+        return json!({ "vec": map_idl_type(inner) });
+    }
+    if let Some(inner) = name.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        if let Some((elem, len)) = inner.rsplit_once(';') {
+            if let Ok(len) = len.trim().parse::<u64>() {
+                return json!({ "array": [map_idl_type(elem.trim()), len] });
             }
-            let name = line.split_whitespace().next().unwrap_or("").to_string();
-            program_info.errors.push(ErrorInfo {
-                name: name.clone(),
-                code: idx as u32 + 6000,
-                message: name,
-            });
         }
     }
 
-    // 解析普通结构体 (pub struct)
-    let struct_re = regex::Regex::new(r#"pub struct (\w+)\s*\{([^}]+)\}"#).unwrap();
-    for caps in struct_re.captures_iter(content) {
-        let name = caps.get(1).map(|m| m.as_str().to_string()).unwrap_or_default();
-        let fields_str = caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default();
+    match name {
+        "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64" | "i128" | "bool"
+        | "f32" | "f64" => json!(name),
+        "usize" => json!("u64"),
+        "isize" => json!("i64"),
+        "String" | "str" => json!("string"),
+        "Pubkey" => json!("publicKey"),
+        other => json!({ "defined": other }),
+    }
+}
+
+/// 提取 `Wrapper<Inner>` 的内层类型。
+fn wrapped<'a>(name: &'a str, wrapper: &str) -> Option<&'a str> {
+    name.strip_prefix(wrapper)?
+        .strip_prefix('<')?
+        .strip_suffix('>')
+        .map(str::trim)
+}
 
-        let mut fields = Vec::new();
-        for line in fields_str.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
+fn has_attr(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident(name))
+}
+
+fn has_derive(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| {
+        if !attr.path().is_ident("derive") {
+            return false;
+        }
+        let mut found = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(name) {
+                found = true;
             }
-            if let Some((name_part, type_part)) = line.split_once(':') {
-                fields.push(FieldInfo {
-                    name: name_part.trim().to_string(),
-                    type_name: type_part.trim().to_string(),
-                });
+            Ok(())
+        });
+        found
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 独立用 `sha256("global:make_offer")` / `sha256("account:Offer")` 核对过的
+    // 已知值，防止前缀、大小写或截断长度被意外改动。
+    #[test]
+    fn instruction_discriminator_matches_known_value() {
+        assert_eq!(
+            discriminator("global:", "make_offer"),
+            [214, 98, 97, 35, 59, 12, 44, 178]
+        );
+    }
+
+    #[test]
+    fn account_discriminator_matches_known_value() {
+        assert_eq!(
+            discriminator("account:", "Offer"),
+            [215, 88, 60, 71, 170, 162, 73, 229]
+        );
+    }
+
+    /// 取一个 `#[derive(Accounts)]` 结构体里按名字找到的字段约束。
+    fn field_constraints(src: &str, field_name: &str) -> AccountConstraints {
+        let item_struct: ItemStruct = syn::parse_str(src).expect("test struct must parse");
+        struct_fields(&item_struct.fields)
+            .into_iter()
+            .find(|field| field.name == field_name)
+            .expect("field must exist")
+            .constraints
+    }
+
+    #[test]
+    fn account_constraints_survive_at_error_binding() {
+        // `has_one = ... @ Error::Variant` 不是合法的 `syn::Expr`；这里确认它不会
+        // 让整条 `#[account(...)]` 解析失败而丢掉同一字段上的 `mut`。
+        let constraints = field_constraints(
+            r#"struct TakeOffer<'info> {
+                #[account(mut, has_one = maker @ SwapError::NotAuthorized, close = maker)]
+                offer: Account<'info, Offer>,
+            }"#,
+            "offer",
+        );
+        assert!(constraints.is_mut);
+    }
+
+    #[test]
+    fn account_constraints_parse_init_payer_seeds_bump() {
+        let constraints = field_constraints(
+            r#"struct MakeOffer<'info> {
+                #[account(
+                    init,
+                    payer = maker,
+                    space = 8 + Offer::INIT_SPACE,
+                    seeds = [b"offer", maker.key().as_ref(), id.to_le_bytes().as_ref()],
+                    bump
+                )]
+                offer: Account<'info, Offer>,
+            }"#,
+            "offer",
+        );
+        assert!(constraints.is_init);
+        assert!(constraints.bump);
+        assert_eq!(constraints.payer.as_deref(), Some("maker"));
+        assert_eq!(constraints.space.as_deref(), Some("8 + Offer::INIT_SPACE"));
+        assert_eq!(
+            constraints.seeds,
+            vec![
+                "b\"offer\"".to_string(),
+                "maker.key().as_ref()".to_string(),
+                "id.to_le_bytes().as_ref()".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn error_code_variants_use_msg_and_honor_explicit_discriminants() {
+        let item_enum: ItemEnum = syn::parse_str(
+            r#"#[error_code]
+            pub enum SwapError {
+                #[msg("Not authorized to perform this action")]
+                NotAuthorized,
+                #[msg("Offer has expired")]
+                OfferExpired = 10,
+                InvalidAmount,
+            }"#,
+        )
+        .expect("test enum must parse");
+
+        let mut program_info = ProgramInfo {
+            program_id: String::new(),
+            program_name: String::new(),
+            instructions: Vec::new(),
+            accounts: Vec::new(),
+            errors: Vec::new(),
+            structs: Vec::new(),
+        };
+        collect_errors(&item_enum, &mut program_info);
+
+        assert_eq!(program_info.errors.len(), 3);
+
+        assert_eq!(program_info.errors[0].code, 6000);
+        assert_eq!(program_info.errors[0].name, "NotAuthorized");
+        assert_eq!(
+            program_info.errors[0].message,
+            "Not authorized to perform this action"
+        );
+
+        // 显式判别值 `= 10` 覆盖默认的递增计数。
+        assert_eq!(program_info.errors[1].code, 6010);
+        assert_eq!(program_info.errors[1].message, "Offer has expired");
+
+        // 其后的变体在显式判别值的基础上继续递增，没有 `#[msg(...)]` 时退回变体名。
+        assert_eq!(program_info.errors[2].code, 6011);
+        assert_eq!(program_info.errors[2].name, "InvalidAmount");
+        assert_eq!(program_info.errors[2].message, "InvalidAmount");
+    }
+
+    fn field(name: &str, type_name: &str, constraints: AccountConstraints) -> FieldInfo {
+        FieldInfo {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            constraints,
+        }
+    }
+
+    fn account(name: &str, fields: Vec<FieldInfo>) -> AccountInfo {
+        AccountInfo {
+            name: name.to_string(),
+            instruction_args: Vec::new(),
+            fields,
+        }
+    }
+
+    fn program_info_with_accounts(accounts: Vec<AccountInfo>) -> ProgramInfo {
+        ProgramInfo {
+            program_id: String::new(),
+            program_name: String::new(),
+            instructions: Vec::new(),
+            accounts,
+            errors: Vec::new(),
+            structs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn idl_accounts_expand_inlines_composite_accounts() {
+        // `MakeOffer` 内嵌一个可复用的 `TransferAccounts` 组合账户，如请求里的例子。
+        let info = program_info_with_accounts(vec![
+            account(
+                "MakeOffer",
+                vec![
+                    field(
+                        "maker",
+                        "Signer<'info>",
+                        AccountConstraints {
+                            is_signer: true,
+                            ..Default::default()
+                        },
+                    ),
+                    field("transfer", "TransferAccounts<'info>", AccountConstraints::default()),
+                ],
+            ),
+            account(
+                "TransferAccounts",
+                vec![field(
+                    "token_program",
+                    "Program<'info, Token>",
+                    AccountConstraints::default(),
+                )],
+            ),
+        ]);
+
+        let items = idl_accounts_for(&info, "MakeOffer");
+        assert_eq!(items.len(), 2);
+
+        assert!(matches!(
+            &items[0],
+            IdlAccountItem::Single(acc) if acc.name == "maker" && acc.is_signer
+        ));
+
+        match &items[1] {
+            IdlAccountItem::Composite(group) => {
+                assert_eq!(group.name, "transfer");
+                assert_eq!(group.accounts.len(), 1);
+                assert!(matches!(
+                    &group.accounts[0],
+                    IdlAccountItem::Single(acc) if acc.name == "token_program"
+                ));
             }
+            other => panic!("expected a composite group, got {other:?}"),
         }
+    }
+
+    #[test]
+    fn idl_accounts_expand_breaks_cycles() {
+        // `A` 内嵌 `B`，`B` 又内嵌回 `A`：没有 `seen` 守卫会无限递归。
+        let info = program_info_with_accounts(vec![
+            account("A", vec![field("b", "B<'info>", AccountConstraints::default())]),
+            account("B", vec![field("a", "A<'info>", AccountConstraints::default())]),
+        ]);
 
-        program_info.structs.push(StructInfo { name, fields });
+        let items = idl_accounts_for(&info, "A");
+        assert_eq!(items.len(), 1);
+        match &items[0] {
+            IdlAccountItem::Composite(b) => {
+                assert_eq!(b.name, "b");
+                assert_eq!(b.accounts.len(), 1);
+                match &b.accounts[0] {
+                    // 展开到第二次遇到 `A` 时被 `seen` 挡住，内层组为空而不是无限递归。
+                    IdlAccountItem::Composite(a_again) => {
+                        assert_eq!(a_again.name, "a");
+                        assert!(a_again.accounts.is_empty());
+                    }
+                    other => panic!("expected a composite group, got {other:?}"),
+                }
+            }
+            other => panic!("expected a composite group, got {other:?}"),
+        }
     }
 
-    program_info
-}
\ No newline at end of file
+    #[test]
+    fn map_idl_type_covers_common_shapes() {
+        use serde_json::json;
+
+        assert_eq!(map_idl_type("u64"), json!("u64"));
+        assert_eq!(map_idl_type("Pubkey"), json!("publicKey"));
+        // Rust 的 `usize`/`isize` 在链上没有固定宽度，Anchor IDL 里映射成定宽整数。
+        assert_eq!(map_idl_type("usize"), json!("u64"));
+        assert_eq!(map_idl_type("isize"), json!("i64"));
+        assert_eq!(map_idl_type("Option<u64>"), json!({ "option": "u64" }));
+        assert_eq!(map_idl_type("Vec<Pubkey>"), json!({ "vec": "publicKey" }));
+        assert_eq!(
+            map_idl_type("[u8; 32]"),
+            json!({ "array": ["u8", 32] })
+        );
+        // 未识别的类型名（学生自定义的结构体/枚举）原样作为 `defined` 引用。
+        assert_eq!(map_idl_type("Offer"), json!({ "defined": "Offer" }));
+    }
+}